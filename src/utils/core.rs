@@ -4,9 +4,23 @@
 //
 // - Reading container configuration from a state file (e.g., DockerHub image, port, and mounts).
 // - Downloading and unpacking Docker images directly from Docker Hub.
+// - Caching layers in a content-addressable store, keyed by sha256 digest, with a JSON metadata
+//   index so repeated launches can reconstruct a rootfs without re-hitting the registry.
 // - Setting up a root filesystem for the container with pivot_root and custom mount points.
 // - Isolating the container process with Linux namespaces (PID, NET, UTS, and mount).
-// - Running a simple process inside the container.
+// - Honoring the image's own config (entrypoint, cmd, env, working dir, user) instead of running
+//   a fixed debug command.
+// - Resolving multi-arch manifest lists / OCI image indexes to the host's (or an overridden)
+//   platform before fetching layers.
+// - Downloading layers concurrently through a bounded worker pool, then extracting them in
+//   strict manifest order.
+// - Applying OCI whiteout semantics (deleted files, opaque directories) while assembling a
+//   rootfs, with an optional overlayfs-backed mode that mounts cached layers instead of copying.
+// - Describing several cooperating containers in one state file ("services:"), each with its own
+//   image/port/mounts/env, started in dependency order derived from a "depends_on" list.
+// - Pulling from a configurable registry host or pull-through mirror, authenticating against
+//   whatever auth realm the registry's own 401 challenge points to (instead of assuming Docker
+//   Hub), with optional HTTP Basic credentials or a pre-supplied bearer token for private repos.
 //
 // Key features of the implementation:
 // 1. Direct interaction with system-level APIs using the `nix` crate.
@@ -17,8 +31,10 @@
 // Dependencies:
 // - nix: System call abstractions for Unix-like systems.
 // - reqwest: HTTP client for communicating with Docker Hub.
-// - serde/serde_yaml: Parsing state file configurations.
+// - serde/serde_yaml/serde_json: Parsing state file and metadata configurations.
+// - sha2: Verifying layer blob digests against the manifest.
 // - tar: Extracting Docker image layers.
+// - xattr: Setting the overlayfs opaque-directory marker in overlay mode.
 //
 // Note: This is a proof-of-concept runtime and lacks advanced features like cgroups or robust error
 // handling for production use.
@@ -27,63 +43,649 @@
 
 use nix::mount::{mount, umount2, MntFlags, MsFlags};
 use nix::sched::{unshare, CloneFlags};
-use nix::sys::wait::waitpid;
-use nix::unistd::{chdir, execvp, fork, mkdir, pivot_root, sethostname, ForkResult};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{
+    chdir, close, execvpe, fork, mkdir, pipe, pivot_root, read, sethostname, write, ForkResult,
+    Gid, Pid, Uid,
+};
 use reqwest::blocking::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_yaml;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
 use std::ffi::CString;
 use std::fs::{create_dir_all, File};
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, Write};
 use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 use tar::Archive;
 
-// Statefile structure
+// Root of the content-addressable layer store. Each layer blob lives at
+// `<LAYER_STORE_DIR>/<digest>.blob`, keyed by the sha256 digest exactly as it appears in the manifest
+// (including the "sha256:" prefix, colon replaced so it is a valid path component).
+const LAYER_STORE_DIR: &str = "/var/lib/containers/layers";
+
+// JSON index mapping "image:tag" to the ordered list of layer digests + config digest that make
+// up that image, so a rootfs can be reconstructed from the layer store without the registry.
+const METADATA_PATH: &str = "/var/lib/containers/metadata.json";
+
+// Statefile structure. Supports two shapes:
+// - The original single-container form (`container`/`port`/... at the top level).
+// - A compose-style multi-service form (`services: { name: { container, port, depends_on, ... } }`)
+//   for bringing up several cooperating containers with a start order derived from `depends_on`.
+// `service_graph` is the single place that reconciles them; when `services` is set, the top-level
+// single-container fields are ignored rather than rejected.
 #[derive(Deserialize)]
 struct StateFile {
-    container: String,         // Full DockerHub image name with optional tag
-    port: u16,                 // Port for localhost binding
-    mounts: Option<Vec<Mount>>, // Optional list of mounts
+    container: Option<String>, // Full DockerHub image name with optional tag (single-container form)
+    port: Option<u16>,         // Port for localhost binding (single-container form)
+    mounts: Option<Vec<Mount>>, // Optional list of mounts (single-container form)
+    platform: Option<String>,  // Optional "os/arch" override, e.g. "linux/arm64", for cross-arch pulls
+    download_concurrency: Option<usize>, // Max in-flight layer downloads, defaults to DEFAULT_DOWNLOAD_CONCURRENCY
+    rootfs_mode: Option<String>, // "copy" (default) extracts layers into a per-image dir; "overlay" mounts them
+    env: Option<Vec<String>>,  // Extra "KEY=VALUE" entries layered over the image's own Env (single-container form)
+    registry: Option<RegistryConfig>, // Registry host/auth override (single-container form)
+    services: Option<HashMap<String, ServiceSpec>>, // Compose-style multi-service form
 }
 
-#[derive(Deserialize)]
+// One service in the compose-style form. Mirrors the fields the single-container form has at the
+// top level, plus `depends_on` to express start ordering between services.
+#[derive(Deserialize, Clone)]
+struct ServiceSpec {
+    container: String,
+    port: u16,
+    mounts: Option<Vec<Mount>>,
+    platform: Option<String>,
+    download_concurrency: Option<usize>,
+    rootfs_mode: Option<String>,
+    env: Option<Vec<String>>,
+    registry: Option<RegistryConfig>,
+    depends_on: Option<Vec<String>>, // Names of services that must be started first
+}
+
+impl StateFile {
+    // Normalizes either shape into a name -> ServiceSpec map. The single-container form becomes a
+    // one-service graph under the name "default", with no dependencies.
+    fn service_graph(&self) -> Result<HashMap<String, ServiceSpec>, Box<dyn std::error::Error>> {
+        if let Some(services) = &self.services {
+            if services.is_empty() {
+                return Err("state file \"services\" must not be empty".into());
+            }
+            return Ok(services.clone());
+        }
+
+        let container = self
+            .container
+            .clone()
+            .ok_or("state file must set either \"container\" or \"services\"")?;
+        let port = self
+            .port
+            .ok_or("state file must set \"port\" when using the single-container form")?;
+        let mut graph = HashMap::new();
+        graph.insert(
+            "default".to_string(),
+            ServiceSpec {
+                container,
+                port,
+                mounts: self.mounts.clone(),
+                platform: self.platform.clone(),
+                download_concurrency: self.download_concurrency,
+                rootfs_mode: self.rootfs_mode.clone(),
+                env: self.env.clone(),
+                registry: self.registry.clone(),
+                depends_on: None,
+            },
+        );
+        Ok(graph)
+    }
+}
+
+// Topologically sorts services by `depends_on` (a dependency must start before anything that
+// depends on it), using Kahn's algorithm. Ties are broken by name so the order is deterministic.
+// Errors if a service depends on a name that doesn't exist, or if the dependencies form a cycle.
+fn topo_sort_services(
+    services: &HashMap<String, ServiceSpec>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut in_degree: HashMap<&str, usize> = services.keys().map(|name| (name.as_str(), 0)).collect();
+    let mut dependents: HashMap<&str, Vec<&str>> = services.keys().map(|name| (name.as_str(), Vec::new())).collect();
+
+    for (name, spec) in services {
+        for dep in spec.depends_on.as_deref().unwrap_or(&[]) {
+            if !services.contains_key(dep) {
+                return Err(format!("service \"{}\" depends on unknown service \"{}\"", name, dep).into());
+            }
+            *in_degree.get_mut(name.as_str()).unwrap() += 1;
+            dependents.get_mut(dep.as_str()).unwrap().push(name.as_str());
+        }
+    }
+
+    let mut ready: Vec<&str> = in_degree
+        .iter()
+        .filter(|(_, degree)| **degree == 0)
+        .map(|(name, _)| *name)
+        .collect();
+    ready.sort_unstable();
+    let mut queue: VecDeque<&str> = ready.into();
+
+    let mut sorted = Vec::with_capacity(services.len());
+    while let Some(name) = queue.pop_front() {
+        sorted.push(name.to_string());
+
+        let mut newly_ready: Vec<&str> = Vec::new();
+        for dependent in &dependents[name] {
+            let degree = in_degree.get_mut(dependent).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                newly_ready.push(dependent);
+            }
+        }
+        newly_ready.sort_unstable();
+        queue.extend(newly_ready);
+    }
+
+    if sorted.len() != services.len() {
+        return Err("dependency cycle detected among services".into());
+    }
+    Ok(sorted)
+}
+
+// Layers `overrides` ("KEY=VALUE" entries from the state file) on top of the image's own `base`
+// env, replacing any key that appears in both. Order of `base` entries is preserved; new keys from
+// `overrides` are appended.
+fn merge_env(base: &Option<Vec<String>>, overrides: &Option<Vec<String>>) -> Option<Vec<String>> {
+    let overrides = match overrides {
+        Some(overrides) if !overrides.is_empty() => overrides,
+        _ => return base.clone(),
+    };
+
+    fn key_of(entry: &str) -> &str {
+        entry.split_once('=').map(|(k, _)| k).unwrap_or(entry)
+    }
+
+    let mut merged: Vec<String> = base.clone().unwrap_or_default();
+    for entry in overrides {
+        let key = key_of(entry);
+        match merged.iter_mut().find(|existing| key_of(existing) == key) {
+            Some(existing) => *existing = entry.clone(),
+            None => merged.push(entry.clone()),
+        }
+    }
+    Some(merged)
+}
+
+// How the rootfs for a container start is assembled from cached layers.
+enum RootFsMode {
+    // Extract every layer into a dedicated per-image directory, applying whiteouts as we go. The
+    // extraction happens once; later starts reuse that directory as it is, including any changes
+    // earlier containers made to it.
+    Copy,
+    // Extract each layer once into its own directory and mount an overlayfs over them. Every
+    // start after the first is just a mount syscall.
+    Overlay,
+}
+
+impl RootFsMode {
+    fn from_state(rootfs_mode: &Option<String>) -> Self {
+        match rootfs_mode.as_deref() {
+            Some("overlay") => RootFsMode::Overlay,
+            _ => RootFsMode::Copy,
+        }
+    }
+}
+
+// Default number of layer blobs downloaded concurrently when the state file doesn't override it.
+const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 4;
+
+// Registry host used when the state file doesn't configure one.
+const DEFAULT_REGISTRY_HOST: &str = "registry-1.docker.io";
+
+// Describes which registry to pull from and how to authenticate to it. All fields are optional so
+// the default (anonymous Docker Hub) keeps working with no "registry:" section at all.
+#[derive(Deserialize, Clone, Default)]
+struct RegistryConfig {
+    host: Option<String>, // Registry host talked to for auth + content, defaults to Docker Hub
+    mirror: Option<String>, // Pull-through mirror host; overrides `host` for manifest/blob requests
+    username: Option<String>, // HTTP Basic credentials presented to the auth realm, for private repos
+    password: Option<String>,
+    token: Option<String>, // Pre-supplied bearer token; skips the auth realm entirely when set
+}
+
+impl RegistryConfig {
+    fn registry_host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_REGISTRY_HOST)
+    }
+
+    // The host manifest/blob requests are actually sent to: the configured mirror if any,
+    // otherwise the registry itself.
+    fn content_host(&self) -> &str {
+        self.mirror.as_deref().unwrap_or_else(|| self.registry_host())
+    }
+
+    // A short, path-safe fingerprint of which registry this config pulls from and whether it
+    // authenticates. Two configs that resolve to different content hosts, or the same host with
+    // and without credentials, never share a cached pull - the content each one serves under
+    // the same image:tag can be completely different. Which credentials are set is not part of
+    // the fingerprint.
+    fn identity(&self) -> String {
+        let has_credentials = self.token.is_some() || self.username.is_some();
+        format!(
+            "{}{}",
+            self.content_host().replace(['/', ':'], "_"),
+            if has_credentials { "_auth" } else { "" }
+        )
+    }
+}
+
+#[derive(Deserialize, Clone)]
 struct Mount {
     source: String, // Path on the host
     target: String, // Path in the container
 }
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let overall_start = Instant::now();
+// The subset of the OCI image config's `config` section we care about for launching the
+// container. Field names match the JSON as published by the registry (PascalCase).
+#[derive(Deserialize, Clone, Default)]
+struct ImageConfig {
+    #[serde(rename = "Entrypoint")]
+    entrypoint: Option<Vec<String>>,
+    #[serde(rename = "Cmd")]
+    cmd: Option<Vec<String>>,
+    #[serde(rename = "Env")]
+    env: Option<Vec<String>>,
+    #[serde(rename = "WorkingDir")]
+    working_dir: Option<String>,
+    #[serde(rename = "User")]
+    user: Option<String>,
+}
 
+// Records how an "image:tag" maps to the layers (and config) that make it up, so a later launch
+// can reconstruct the rootfs purely from the content-addressable store.
+#[derive(Serialize, Deserialize, Clone)]
+struct ImageMetadata {
+    config_digest: String,
+    layers: Vec<String>, // ordered bottom-to-top, as in the manifest
+}
+
+// Thin wrapper around the on-disk JSON index (METADATA_PATH). Kept as a flat HashMap rather than
+// anything fancier since the whole point is a simple, inspectable "image:tag" -> layers mapping.
+struct MetadataManager {
+    index: HashMap<String, ImageMetadata>,
+}
+
+impl MetadataManager {
+    fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        if !Path::new(METADATA_PATH).exists() {
+            return Ok(Self {
+                index: HashMap::new(),
+            });
+        }
+        let file = File::open(METADATA_PATH)?;
+        let index = serde_json::from_reader(BufReader::new(file))?;
+        Ok(Self { index })
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = Path::new(METADATA_PATH).parent() {
+            create_dir_all(parent)?;
+        }
+        let file = File::create(METADATA_PATH)?;
+        serde_json::to_writer_pretty(file, &self.index)?;
+        Ok(())
+    }
+
+    fn get(&self, image_with_tag: &str) -> Option<&ImageMetadata> {
+        self.index.get(image_with_tag)
+    }
+
+    fn insert(&mut self, image_with_tag: String, metadata: ImageMetadata) {
+        self.index.insert(image_with_tag, metadata);
+    }
+}
+
+// Turns a manifest digest ("sha256:abcd...") into a safe, flat path component.
+fn digest_to_path_component(digest: &str) -> String {
+    digest.replace(':', "_")
+}
+
+// Path to a blob's raw bytes in the content store (used for both layer tarballs and the config
+// blob alike).
+fn blob_store_path(digest: &str) -> String {
+    format!("{}/{}.blob", LAYER_STORE_DIR, digest_to_path_component(digest))
+}
+
+// Path to a layer's extracted-once contents, used as an overlayfs lowerdir so container starts
+// need neither a fresh copy nor a fresh extraction.
+fn layer_extracted_path(digest: &str) -> String {
+    format!("{}/{}/fs", LAYER_STORE_DIR, digest_to_path_component(digest))
+}
+
+// Wraps a writer so every byte passed through it is also fed into a running Sha256 hash. Lets
+// `io::copy` hash a blob as it streams to disk, instead of buffering the whole blob (layers can be
+// hundreds of MB) in memory first and hashing it in one shot afterward.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Downloads a single blob into the content-addressable store, verifying its sha256 digest as it
+// streams. Returns early without touching the network if the blob is already cached.
+fn fetch_blob_to_store(
+    client: &Client,
+    content_host: &str,
+    image: &str,
+    token: Option<&str>,
+    digest: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let stored_path = blob_store_path(digest);
+
+    if Path::new(&stored_path).exists() {
+        println!("Layer {} already cached, skipping download", digest);
+        return Ok(std::fs::read(&stored_path)?);
+    }
+
+    create_dir_all(LAYER_STORE_DIR)?;
+
+    let url = format!("https://{}/v2/{}/blobs/{}", content_host, image, digest);
+    println!("Downloading layer: {}", digest);
+    let mut req = client.get(&url);
+    if let Some(token) = token {
+        req = req.bearer_auth(token);
+    }
+    let mut resp = req.send()?;
+
+    // Stream the response body straight to a tmp file, hashing it as it goes, rather than
+    // buffering the whole blob before verifying its digest.
+    let tmp_path = format!("{}.tmp", stored_path);
+    let mut hashing_writer = HashingWriter {
+        inner: File::create(&tmp_path)?,
+        hasher: Sha256::new(),
+    };
+    io::copy(&mut resp, &mut hashing_writer)?;
+    let computed = format!("{:x}", hashing_writer.hasher.finalize());
+
+    let expected = digest.strip_prefix("sha256:").ok_or("Unsupported digest algorithm")?;
+    if computed != expected {
+        std::fs::remove_file(&tmp_path)?;
+        return Err(format!(
+            "Digest mismatch for layer {}: expected {}, got {}",
+            digest, expected, computed
+        )
+        .into());
+    }
+
+    std::fs::rename(&tmp_path, &stored_path)?;
+
+    Ok(std::fs::read(&stored_path)?)
+}
+
+// Fetches the image config blob (cached like any other blob) and extracts its `config` section.
+fn fetch_image_config(
+    client: &Client,
+    content_host: &str,
+    image: &str,
+    token: Option<&str>,
+    config_digest: &str,
+) -> Result<ImageConfig, Box<dyn std::error::Error>> {
+    let data = fetch_blob_to_store(client, content_host, image, token, config_digest)?;
+    let config_blob: Value = serde_json::from_slice(&data)?;
+    let image_config: ImageConfig = serde_json::from_value(
+        config_blob
+            .get("config")
+            .cloned()
+            .ok_or("Image config blob missing \"config\" section")?,
+    )?;
+    Ok(image_config)
+}
+
+// Reads a cached config blob straight from the content store, for the already-downloaded path.
+fn read_cached_image_config(config_digest: &str) -> Result<ImageConfig, Box<dyn std::error::Error>> {
+    let data = std::fs::read(blob_store_path(config_digest))?;
+    let config_blob: Value = serde_json::from_slice(&data)?;
+    let image_config: ImageConfig = serde_json::from_value(
+        config_blob
+            .get("config")
+            .cloned()
+            .ok_or("Image config blob missing \"config\" section")?,
+    )?;
+    Ok(image_config)
+}
+
+// OCI whiteout marker naming (see the image-spec): a layer deletes a lower layer's file by
+// shipping an empty `.wh.<name>` entry next to it, and resets a whole directory by shipping
+// `.wh..wh..opq` inside it.
+const OPAQUE_WHITEOUT_NAME: &str = ".wh..wh..opq";
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+// Removes whatever is at `path` (file, symlink, or directory tree), tolerating it already being
+// absent since a whiteout for something the base layer never created is a no-op.
+fn remove_path_if_present(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(path)?,
+        Ok(_) => std::fs::remove_file(path)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    Ok(())
+}
+
+// Unpacks a single layer tarball on top of `dest`, honoring OCI whiteout semantics: `.wh.<name>`
+// deletes `<name>` from the already-assembled rootfs instead of being extracted as a real file,
+// and `.wh..wh..opq` clears out whatever prior layers put in that directory (an "opaque" reset)
+// before this layer's own entries for it are applied.
+fn unpack_layer_with_whiteouts(
+    tar_data: &[u8],
+    dest: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut archive = Archive::new(io::Cursor::new(tar_data));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let file_name = entry_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let parent = entry_path.parent().unwrap_or_else(|| Path::new(""));
+
+        if file_name == OPAQUE_WHITEOUT_NAME {
+            let opaque_dir = Path::new(dest).join(parent);
+            println!("Applying opaque whiteout for {}", opaque_dir.display());
+            if opaque_dir.is_dir() {
+                for child in std::fs::read_dir(&opaque_dir)? {
+                    remove_path_if_present(&child?.path())?;
+                }
+            }
+            continue;
+        }
+
+        if let Some(deleted_name) = file_name.strip_prefix(WHITEOUT_PREFIX) {
+            let target = Path::new(dest).join(parent).join(deleted_name);
+            println!("Applying whiteout for {}", target.display());
+            remove_path_if_present(&target)?;
+            continue;
+        }
+
+        entry.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+// Concatenates entrypoint + cmd per OCI rules: if an entrypoint is set, cmd (if any) is appended
+// as its arguments; otherwise cmd alone is the argv. Falls back to the old debug shell command
+// when the image specifies neither, so images that genuinely ship nothing still start.
+fn resolve_argv(image_config: &ImageConfig) -> Vec<String> {
+    match (&image_config.entrypoint, &image_config.cmd) {
+        (Some(entrypoint), Some(cmd)) if !entrypoint.is_empty() => {
+            entrypoint.iter().chain(cmd.iter()).cloned().collect()
+        }
+        (Some(entrypoint), _) if !entrypoint.is_empty() => entrypoint.clone(),
+        (_, Some(cmd)) if !cmd.is_empty() => cmd.clone(),
+        _ => vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            "ip link set lo up && echo Hello from container! && sleep 10".to_string(),
+        ],
+    }
+}
+
+// Parses the image config's `User` field ("uid", "uid:gid", or a username). We don't have access
+// to the container's /etc/passwd from here, so only numeric forms resolve; anything else (or an
+// absent User) falls back to the previous hardcoded "nobody" (65534:65534).
+fn resolve_user(image_config: &ImageConfig) -> (Uid, Gid) {
+    const NOBODY: u32 = 65534;
+    let user = match &image_config.user {
+        Some(user) if !user.is_empty() => user,
+        _ => return (Uid::from_raw(NOBODY), Gid::from_raw(NOBODY)),
+    };
+
+    let mut parts = user.splitn(2, ':');
+    let uid = parts.next().and_then(|s| s.parse::<u32>().ok());
+    let gid = parts.next().and_then(|s| s.parse::<u32>().ok());
+
+    match uid {
+        Some(uid) => (Uid::from_raw(uid), Gid::from_raw(gid.unwrap_or(uid))),
+        None => (Uid::from_raw(NOBODY), Gid::from_raw(NOBODY)),
+    }
+}
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Path to the state file
     let state_file_path = "state.yaml";
 
-    // Read container and port from the state file
     let state = read_state_file(state_file_path)?;
-    let container_with_tag = ensure_tag(&state.container);
-    let port = state.port;
+    let services = state.service_graph()?;
+    let start_order = topo_sort_services(&services)?;
+
+    if start_order.len() > 1 {
+        println!("Starting {} services in order: {}", start_order.len(), start_order.join(", "));
+    }
+
+    // Launch every service in dependency order, but `run_service` only blocks long enough to see
+    // each one actually start - not until it exits - so a long-running service (a database, a web
+    // server) doesn't starve everything that depends on it from ever being prepared. Once
+    // everything is running, wait for them all to finish (or fail) together.
+    let mut running = Vec::with_capacity(start_order.len());
+    for name in &start_order {
+        let spec = &services[name];
+        if start_order.len() > 1 {
+            println!("\n==> Starting service \"{}\"", name);
+        }
+        let child = run_service(name, spec)?;
+        running.push((name.clone(), child));
+    }
+
+    for (name, child) in running {
+        match waitpid(child, None)? {
+            WaitStatus::Exited(_, 0) => {}
+            status => {
+                return Err(format!("service \"{}\" container exited abnormally: {:?}", name, status).into())
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Pulls, assembles and starts a single service (one call per entry in the topologically sorted
+// service graph), reporting the same benchmarks the original single-container `run` did. Returns
+// as soon as the service's container has actually started, not once it exits - returning the
+// supervising child's PID so the caller can wait for its eventual exit separately, without that
+// wait blocking the rest of the service graph from being brought up.
+fn run_service(name: &str, spec: &ServiceSpec) -> Result<Pid, Box<dyn std::error::Error>> {
+    let overall_start = Instant::now();
+
+    let container_with_tag = ensure_tag(&spec.container);
+    let port = spec.port;
 
     // Prepare root filesystem and measure times
+    let download_concurrency = spec
+        .download_concurrency
+        .unwrap_or(DEFAULT_DOWNLOAD_CONCURRENCY);
+    let rootfs_mode = RootFsMode::from_state(&spec.rootfs_mode);
+    let registry = spec.registry.clone().unwrap_or_default();
     let prepare_start = Instant::now();
-    let (root_fs, download_duration, unpack_duration) = prepare_root_fs(&container_with_tag)?;
+    let (root_fs, download_wall_clock, download_summed, unpack_duration, mut image_config) =
+        prepare_root_fs(&container_with_tag, &spec.platform, download_concurrency, &rootfs_mode, &registry)?;
     let prepare_duration = prepare_start.elapsed();
 
-    // Start the container with direct Linux APIs
+    // Layer any service-level env overrides from the state file on top of the image's own Env.
+    image_config.env = merge_env(&image_config.env, &spec.env);
+
+    // Start the container with direct Linux APIs. `start_container` unshares namespaces and
+    // pivots the root of whatever process calls it, so it must run in a forked child rather than
+    // the orchestrator itself - otherwise the orchestrator's own mount/net/PID namespaces and
+    // filesystem view would be gone before it gets to prepare any later services in the start
+    // order. The child always exits explicitly so it never falls back into the orchestrator's
+    // service loop.
+    //
+    // A pipe signals readiness: the child writes one byte once the container's own workload has
+    // actually launched (see `start_container`'s internal fork), and we only block here until
+    // that happens, not until the workload exits. Reading zero bytes means the child exited (or
+    // crashed) before ever getting that far, so we reap it and surface its exit status instead.
+    let (ready_read, ready_write) = pipe()?;
     let start_time = Instant::now();
-    start_container(&root_fs, state.mounts)?;
+    let child_pid = match fork()? {
+        ForkResult::Parent { child } => {
+            close(ready_write)?;
+            let mut ready_byte = [0u8; 1];
+            let got_ready = read(ready_read, &mut ready_byte).unwrap_or(0) > 0;
+            close(ready_read)?;
+            if !got_ready {
+                match waitpid(child, None)? {
+                    WaitStatus::Exited(_, 0) => {}
+                    status => {
+                        return Err(format!(
+                            "service \"{}\" container setup failed ({:?})",
+                            name, status
+                        )
+                        .into())
+                    }
+                }
+            }
+            child
+        }
+        ForkResult::Child => {
+            close(ready_read)?;
+            match start_container(&root_fs, spec.mounts.clone(), &image_config, Some(ready_write)) {
+                Ok(()) => std::process::exit(0),
+                Err(err) => {
+                    eprintln!("service \"{}\" failed to start: {}", name, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+    };
     let start_duration = start_time.elapsed();
 
     // Always output the URL of the running container
-    println!("Container is running at: http://localhost:{}", port);
+    println!("Service \"{}\" is running at: http://localhost:{}", name, port);
 
     // Performance benchmarks
     let overall_duration = overall_start.elapsed();
-    println!("\nPerformance Benchmarks:");
+    println!("\nPerformance Benchmarks ({}):", name);
     println!(
-        "  - Download time: {:.2} seconds",
-        download_duration.as_secs_f64()
+        "  - Download time (wall-clock, {} parallel workers): {:.2} seconds",
+        download_concurrency,
+        download_wall_clock.as_secs_f64()
+    );
+    println!(
+        "  - Download time (summed across layers, serial-equivalent): {:.2} seconds",
+        download_summed.as_secs_f64()
     );
     println!(
         "  - Unpack time: {:.2} seconds",
@@ -102,7 +704,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         overall_duration.as_secs_f64()
     );
 
-    Ok(())
+    Ok(child_pid)
 }
 
 // Reads the state file containing the Docker image name, port, and mounts
@@ -122,107 +724,564 @@ fn ensure_tag(container: &str) -> String {
     }
 }
 
-// Downloads and extracts a Docker image directly from Docker Hub
-fn download_image(
+// Maps Rust's `std::env::consts::ARCH` naming onto the arch strings used in OCI/Docker manifest
+// platform entries (they mostly agree, but the two common exceptions are worth calling out).
+fn normalize_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => other,
+    }
+}
+
+// Resolves the (os, arch) pair to select a sub-manifest for, preferring an explicit override from
+// the state file over the host's own platform.
+fn target_platform(platform_override: &Option<String>) -> (String, String) {
+    if let Some(platform) = platform_override {
+        if let Some((os, arch)) = platform.split_once('/') {
+            return (os.to_string(), arch.to_string());
+        }
+    }
+    (
+        std::env::consts::OS.to_string(),
+        normalize_arch(std::env::consts::ARCH).to_string(),
+    )
+}
+
+// The metadata index key for an image:tag pulled for a given platform from a given registry. Must
+// incorporate both the resolved platform and the registry's identity (not just image:tag) -
+// otherwise a second pull with a different `platform_override`, or a different `registry:`
+// pointing at a same-named but unrelated image, would see the first pull's cached layers/config
+// and silently reuse them instead of fetching the ones actually being asked for.
+fn image_metadata_key(
     image: &str,
     tag: &str,
-    root_fs_path: &str,
-) -> Result<(Duration, Duration), Box<dyn std::error::Error>> {
-    let client = Client::new();
+    platform_override: &Option<String>,
+    registry: &RegistryConfig,
+) -> String {
+    let (target_os, target_arch) = target_platform(platform_override);
+    format!(
+        "{}:{}:{}/{}:{}",
+        image, tag, target_os, target_arch, registry.identity()
+    )
+}
 
-    // Authenticate and get a token
-    let token_url = format!(
-        "https://auth.docker.io/token?service=registry.docker.io&scope=repository:{}:pull",
-        image
-    );
-    let token_resp: Value = client.get(&token_url).send()?.json()?;
+// Given a manifest list / OCI image index response, picks the sub-manifest matching the target
+// platform and returns its digest.
+fn select_manifest_digest<'a>(
+    manifest_list: &'a Value,
+    target_os: &str,
+    target_arch: &str,
+) -> Result<&'a str, Box<dyn std::error::Error>> {
+    let manifests = manifest_list["manifests"]
+        .as_array()
+        .ok_or("Manifest list missing \"manifests\" array")?;
+    manifests
+        .iter()
+        .find(|entry| {
+            entry["platform"]["os"].as_str() == Some(target_os)
+                && entry["platform"]["architecture"].as_str() == Some(target_arch)
+        })
+        .and_then(|entry| entry["digest"].as_str())
+        .ok_or_else(|| {
+            format!(
+                "No manifest in list matches platform {}/{}",
+                target_os, target_arch
+            )
+            .into()
+        })
+}
+
+// Splits a `WWW-Authenticate: Bearer realm="...",service="...",...` header into (realm, service).
+// We ignore any `scope` the challenge itself suggests and build a repository-scoped one ourselves,
+// since the challenge from a bare `/v2/` ping has no way to know which repository we actually want.
+fn parse_bearer_challenge(header: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let rest = header
+        .strip_prefix("Bearer ")
+        .ok_or("Unsupported WWW-Authenticate scheme (expected Bearer)")?;
+
+    let mut realm = None;
+    let mut service = None;
+    for param in rest.split(',') {
+        if let Some((key, value)) = param.trim().split_once('=') {
+            let value = value.trim_matches('"');
+            match key {
+                "realm" => realm = Some(value.to_string()),
+                "service" => service = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    Ok((
+        realm.ok_or("WWW-Authenticate challenge missing \"realm\"")?,
+        service.unwrap_or_default(),
+    ))
+}
+
+// Obtains a bearer token for pulling `image`, or `None` if the registry doesn't require auth.
+// If the state file already supplies a token, that's used as-is. Otherwise this pings the
+// registry's `/v2/` endpoint, parses the `WWW-Authenticate` challenge it replies with to discover
+// the auth realm (rather than assuming Docker Hub's fixed token URL), and exchanges it for a
+// repository-scoped pull token, attaching HTTP Basic credentials for private repos if configured.
+fn authenticate(
+    client: &Client,
+    registry: &RegistryConfig,
+    image: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if let Some(token) = &registry.token {
+        return Ok(Some(token.clone()));
+    }
+
+    let ping_url = format!("https://{}/v2/", registry.registry_host());
+    let ping_resp = client.get(&ping_url).send()?;
+    if ping_resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(None); // Registry didn't challenge us; assume it doesn't require auth.
+    }
+    let challenge = ping_resp
+        .headers()
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("Registry returned 401 without a WWW-Authenticate challenge")?
+        .to_string();
+    let (realm, service) = parse_bearer_challenge(&challenge)?;
+
+    let scope = format!("repository:{}:pull", image);
+    let mut token_req = client
+        .get(&realm)
+        .query(&[("service", service.as_str()), ("scope", scope.as_str())]);
+    if let (Some(username), Some(password)) = (&registry.username, &registry.password) {
+        token_req = token_req.basic_auth(username, Some(password));
+    }
+    let token_resp: Value = token_req.send()?.json()?;
     let token = token_resp["token"]
         .as_str()
         .or_else(|| token_resp["access_token"].as_str())
         .ok_or("Failed to get access token")?;
+    Ok(Some(token.to_string()))
+}
 
-    // Get the manifest
-    let manifest_url = format!(
-        "https://registry-1.docker.io/v2/{}/manifests/{}",
-        image, tag
-    );
-    let manifest_resp: Value = client
-        .get(&manifest_url)
-        .bearer_auth(token)
-        .header(
-            "Accept",
-            "application/vnd.docker.distribution.manifest.v2+json",
-        )
-        .send()?
-        .json()?;
+// Pulls an image's layers and config into the content-addressable store (network fetches are
+// skipped for anything already cached) and records it in the metadata index. Unlike the old
+// all-in-one `download_image`, this never touches a rootfs directory itself — assembling a
+// rootfs from the resulting `ImageMetadata` is a separate, interchangeable step (see
+// `assemble_root_fs_from_cache` and `assemble_root_fs_with_overlay`).
+fn pull_image(
+    image: &str,
+    tag: &str,
+    platform_override: &Option<String>,
+    download_concurrency: usize,
+    registry: &RegistryConfig,
+) -> Result<(Duration, Duration, ImageConfig, ImageMetadata), Box<dyn std::error::Error>> {
+    let client = Client::new();
+    let content_host = registry.content_host();
+
+    // Authenticate against the registry's own auth realm (discovered via a 401 challenge, or
+    // skipped entirely if a token was already supplied in the state file).
+    let token = authenticate(&client, registry, image)?;
+
+    // Get the manifest. Accept both regular manifests and the "fat" manifest lists / OCI image
+    // indexes that multi-arch images publish instead.
+    const MANIFEST_ACCEPT: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+         application/vnd.docker.distribution.manifest.list.v2+json, \
+         application/vnd.oci.image.manifest.v1+json, \
+         application/vnd.oci.image.index.v1+json";
+    let manifest_url = format!("https://{}/v2/{}/manifests/{}", content_host, image, tag);
+    let mut manifest_req = client.get(&manifest_url).header("Accept", MANIFEST_ACCEPT);
+    if let Some(token) = &token {
+        manifest_req = manifest_req.bearer_auth(token);
+    }
+    let manifest_resp: Value = manifest_req.send()?.json()?;
+
+    // If we got a manifest list / image index back, pick the sub-manifest for the target
+    // platform and fetch that instead.
+    let media_type = manifest_resp["mediaType"].as_str().unwrap_or("");
+    let manifest_resp = if media_type == "application/vnd.docker.distribution.manifest.list.v2+json"
+        || media_type == "application/vnd.oci.image.index.v1+json"
+    {
+        let (target_os, target_arch) = target_platform(platform_override);
+        let sub_digest = select_manifest_digest(&manifest_resp, &target_os, &target_arch)?;
+        println!(
+            "Selected {}/{} manifest {} from manifest list",
+            target_os, target_arch, sub_digest
+        );
+        let sub_manifest_url = format!("https://{}/v2/{}/manifests/{}", content_host, image, sub_digest);
+        let mut sub_manifest_req = client.get(&sub_manifest_url).header("Accept", MANIFEST_ACCEPT);
+        if let Some(token) = &token {
+            sub_manifest_req = sub_manifest_req.bearer_auth(token);
+        }
+        sub_manifest_req.send()?.json()?
+    } else {
+        manifest_resp
+    };
 
-    // Extract layers
+    // Extract layers and config digest
     let layers = manifest_resp["layers"]
         .as_array()
         .ok_or("Failed to get layers from manifest")?;
+    let config_digest = manifest_resp["config"]["digest"]
+        .as_str()
+        .ok_or("Failed to get config digest from manifest")?
+        .to_string();
+
+    let layer_digests: Vec<String> = layers
+        .iter()
+        .map(|layer| {
+            layer["digest"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| "Failed to get layer digest".into())
+        })
+        .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+
+    // Download every blob into the content store concurrently (bounded worker pool).
+    let (download_wall_clock, total_download_duration) = download_layers_parallel(
+        &client,
+        content_host,
+        image,
+        token.as_deref(),
+        &layer_digests,
+        download_concurrency,
+    )?;
+
+    println!("Image {}:{} pulled into the content store", image, tag);
+
+    let image_config = fetch_image_config(&client, content_host, image, token.as_deref(), &config_digest)?;
+
+    let image_metadata = ImageMetadata {
+        config_digest,
+        layers: layer_digests,
+    };
+    let mut metadata = MetadataManager::load()?;
+    metadata.insert(
+        image_metadata_key(image, tag, platform_override, registry),
+        image_metadata.clone(),
+    );
+    metadata.save()?;
+
+    Ok((
+        download_wall_clock,
+        total_download_duration,
+        image_config,
+        image_metadata,
+    ))
+}
+
+// A layer download job handed to a worker thread: which index it was in the manifest (so results
+// can be matched back up even though they complete out of order) and its digest.
+struct LayerDownloadJob {
+    index: usize,
+    digest: String,
+}
+
+// The outcome of downloading one layer: how long the fetch took, for benchmark reporting.
+struct LayerDownloadOutcome {
+    index: usize,
+    duration: Duration,
+}
+
+// Downloads all given layer digests into the content-addressable store concurrently, bounded to
+// `concurrency` in-flight requests at a time. Workers pull jobs off a shared `mpsc` queue and
+// share a cloned `reqwest::blocking::Client`, which is cheap to clone since it pools connections.
+// Returns (wall-clock time for the whole phase, sum of each layer's individual download time) so
+// callers can report both and make the speedup from parallelism visible.
+fn download_layers_parallel(
+    client: &Client,
+    content_host: &str,
+    image: &str,
+    token: Option<&str>,
+    digests: &[String],
+    concurrency: usize,
+) -> Result<(Duration, Duration), Box<dyn std::error::Error>> {
+    let wall_clock_start = Instant::now();
+
+    let (job_tx, job_rx) = mpsc::channel::<LayerDownloadJob>();
+    for (index, digest) in digests.iter().enumerate() {
+        job_tx.send(LayerDownloadJob {
+            index,
+            digest: digest.clone(),
+        })?;
+    }
+    drop(job_tx); // Closing the sender lets workers exit once the queue drains.
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    let (result_tx, result_rx) = mpsc::channel::<Result<LayerDownloadOutcome, String>>();
+    let worker_count = concurrency.max(1).min(digests.len().max(1));
+
+    let workers: Vec<_> = (0..worker_count)
+        .map(|_| {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            let client = client.clone();
+            let content_host = content_host.to_string();
+            let image = image.to_string();
+            let token = token.map(str::to_string);
+            thread::spawn(move || loop {
+                let job = job_rx.lock().unwrap().recv();
+                let job = match job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let download_start = Instant::now();
+                let outcome = fetch_blob_to_store(&client, &content_host, &image, token.as_deref(), &job.digest)
+                    .map(|_| LayerDownloadOutcome {
+                        index: job.index,
+                        duration: download_start.elapsed(),
+                    })
+                    .map_err(|err| format!("layer {}: {}", job.digest, err));
+                // The receiving end only disappears if the main thread already hit an error and
+                // returned early, in which case there's nothing left to report to.
+                let _ = result_tx.send(outcome);
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    // Drain every outcome (success or failure) before deciding what to return. The receiving end
+    // of `result_rx` only closes once every worker has dropped its cloned sender, i.e. once every
+    // worker has exited its loop - so finishing this iteration also means it's safe to join them
+    // below without blocking on work that hasn't been picked up yet. Bailing out on the first
+    // error here instead would leave the rest of the workers running in the background, still
+    // holding the shared job queue and writing into the content store, with no handle left to
+    // join or cancel them.
+    let mut durations = vec![Duration::ZERO; digests.len()];
+    let mut first_error: Option<String> = None;
+    for result in result_rx {
+        match result {
+            Ok(outcome) => durations[outcome.index] = outcome.duration,
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| "layer download worker thread panicked")?;
+    }
+
+    if let Some(err) = first_error {
+        return Err(err.into());
+    }
+
+    let total_download_duration = durations.iter().sum();
+    Ok((wall_clock_start.elapsed(), total_download_duration))
+}
+
+// Reconstructs a rootfs directly from the layer store, in manifest order, without contacting the
+// registry. Only valid once `pull_image` has recorded metadata for this image:tag.
+fn assemble_root_fs_from_cache(
+    metadata: &ImageMetadata,
+    root_fs_path: &str,
+) -> Result<Duration, Box<dyn std::error::Error>> {
     create_dir_all(root_fs_path)?;
+    let unpack_start = Instant::now();
+    for digest in &metadata.layers {
+        let tar_data = std::fs::read(blob_store_path(digest))?;
+        println!("Extracting cached layer: {}", digest);
+        unpack_layer_with_whiteouts(&tar_data, root_fs_path)?;
+    }
+    Ok(unpack_start.elapsed())
+}
 
-    let mut total_download_duration = Duration::ZERO;
-    let mut total_unpack_duration = Duration::ZERO;
-
-    // Sequential download and extraction of layers
-    for layer in layers {
-        let digest = layer["digest"]
-            .as_str()
-            .ok_or("Failed to get layer digest")?;
-        let url = format!(
-            "https://registry-1.docker.io/v2/{}/blobs/{}",
-            image, digest
-        );
+// Extracts a layer into its own directory (once; cached like any other store entry) for use as
+// an overlayfs lowerdir. Unlike the copy-mode path, whiteouts can't just delete a file here -
+// there is no lower layer on disk yet to delete it from - so they're converted into the real
+// marker overlayfs understands: `.wh.<name>` becomes a character device whiteout (mknod 0,0), and
+// `.wh..wh..opq` becomes the `trusted.overlay.opaque` xattr on the directory.
+fn ensure_layer_extracted_for_overlay(digest: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let extracted_dir = layer_extracted_path(digest);
+    let done_marker = Path::new(&extracted_dir).join(".extracted");
+    if done_marker.exists() {
+        return Ok(extracted_dir);
+    }
+
+    create_dir_all(&extracted_dir)?;
+    let tar_data = std::fs::read(blob_store_path(digest))?;
+    let mut archive = Archive::new(io::Cursor::new(tar_data.as_slice()));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let file_name = entry_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let parent = entry_path.parent().unwrap_or_else(|| Path::new(""));
 
-        // Download the layer
-        println!("Downloading layer: {}", digest);
-        let download_start = Instant::now();
-        let layer_resp = client.get(&url).bearer_auth(token).send()?;
-        let tar_data = layer_resp.bytes()?;
-        total_download_duration += download_start.elapsed();
+        if file_name == OPAQUE_WHITEOUT_NAME {
+            let opaque_dir = Path::new(&extracted_dir).join(parent);
+            create_dir_all(&opaque_dir)?;
+            xattr::set(&opaque_dir, "trusted.overlay.opaque", b"y")?;
+            continue;
+        }
 
-        // Extract the layer directly in memory
-        println!("Extracting layer: {}", digest);
-        let unpack_start = Instant::now();
-        let mut archive = Archive::new(io::Cursor::new(tar_data));
-        archive.unpack(root_fs_path)?;
-        total_unpack_duration += unpack_start.elapsed();
+        if let Some(deleted_name) = file_name.strip_prefix(WHITEOUT_PREFIX) {
+            let target = Path::new(&extracted_dir).join(parent).join(deleted_name);
+            if let Some(target_parent) = target.parent() {
+                create_dir_all(target_parent)?;
+            }
+            nix::sys::stat::mknod(
+                &target,
+                nix::sys::stat::SFlag::S_IFCHR,
+                nix::sys::stat::Mode::empty(),
+                0,
+            )?;
+            continue;
+        }
+
+        entry.unpack_in(&extracted_dir)?;
     }
+    File::create(&done_marker)?;
 
-    println!("Image downloaded and extracted to {}", root_fs_path);
+    Ok(extracted_dir)
+}
 
-    Ok((total_download_duration, total_unpack_duration))
+// Checks `/proc/self/mountinfo` for whether `path` is currently a mount point. Used instead of a
+// marker file, which would survive anything that tears down mounts without deleting
+// `/var/lib/containers/...` (e.g. a host reboot) - a marker alone would then lie about the overlay
+// still being mounted when `path` is really just an empty directory.
+fn is_mount_point(path: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let canonical = std::fs::canonicalize(path)?;
+    let canonical = canonical.to_string_lossy();
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(mountinfo
+        .lines()
+        .any(|line| line.split_whitespace().nth(4) == Some(canonical.as_ref())))
 }
 
-// Prepares the root filesystem
-fn prepare_root_fs(
-    image_name: &str,
-) -> Result<(String, Duration, Duration), Box<dyn std::error::Error>> {
-    let sanitized_image_name = image_name.replace("/", "_").replace(":", "_");
-    let root_fs_path = format!("/var/lib/containers/{}", sanitized_image_name);
-    if Path::new(&root_fs_path).exists() {
-        return Ok((root_fs_path, Duration::ZERO, Duration::ZERO));
+// Assembles a rootfs by mounting an overlayfs over per-layer lowerdirs instead of copying or
+// extracting into a fresh directory on every container start - once a layer has been extracted
+// once, every later start of any image sharing it is just a mount syscall.
+fn assemble_root_fs_with_overlay(
+    metadata: &ImageMetadata,
+    overlay_root: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let upper_dir = format!("{}/upper", overlay_root);
+    let work_dir = format!("{}/work", overlay_root);
+    let merged_dir = format!("{}/merged", overlay_root);
+    create_dir_all(&upper_dir)?;
+    create_dir_all(&work_dir)?;
+    create_dir_all(&merged_dir)?;
+
+    // Already mounted from a previous start (e.g. a container that exited without cleanup) -
+    // nothing more to do.
+    if is_mount_point(&merged_dir)? {
+        return Ok(merged_dir);
     }
 
+    // overlayfs' lowerdir= list is ordered highest-priority-first, i.e. topmost layer first; our
+    // layers are stored bottom-to-top as in the manifest, so the list needs reversing.
+    let mut lower_dirs = Vec::with_capacity(metadata.layers.len());
+    for digest in &metadata.layers {
+        lower_dirs.push(ensure_layer_extracted_for_overlay(digest)?);
+    }
+    lower_dirs.reverse();
+
+    let mount_data = format!(
+        "lowerdir={},upperdir={},workdir={}",
+        lower_dirs.join(":"),
+        upper_dir,
+        work_dir
+    );
+    println!("Mounting overlayfs at {}", merged_dir);
+    mount(
+        Some("overlay"),
+        merged_dir.as_str(),
+        Some("overlay"),
+        MsFlags::empty(),
+        Some(mount_data.as_str()),
+    )?;
+
+    Ok(merged_dir)
+}
+
+// Prepares the root filesystem, and resolves the image config (entrypoint/cmd/env/workdir/user)
+// that `start_container` needs to actually run what the image wants rather than a fixed script.
+fn prepare_root_fs(
+    image_name: &str,
+    platform_override: &Option<String>,
+    download_concurrency: usize,
+    rootfs_mode: &RootFsMode,
+    registry: &RegistryConfig,
+) -> Result<(String, Duration, Duration, Duration, ImageConfig), Box<dyn std::error::Error>> {
     // Split image name and tag
     let parts: Vec<&str> = image_name.split(':').collect();
     let image = parts[0];
     let tag = if parts.len() > 1 { parts[1] } else { "latest" };
 
-    // Download and extract image layers
-    let (download_duration, unpack_duration) = download_image(image, tag, &root_fs_path)?;
+    // The rootfs dir and metadata key both carry the resolved platform and the registry's
+    // identity, not just image:tag - a cached pull for one platform, or one registry/mirror, must
+    // never be reused to satisfy a request for another (even under the same image:tag).
+    let (target_os, target_arch) = target_platform(platform_override);
+    let sanitized_image_name = format!(
+        "{}_{}_{}_{}",
+        image_name.replace("/", "_").replace(":", "_"),
+        target_os,
+        target_arch,
+        registry.identity()
+    );
+    let root_fs_path = format!("/var/lib/containers/{}", sanitized_image_name);
+    let metadata_key = image_metadata_key(image, tag, platform_override, registry);
+
+    let stored_metadata = MetadataManager::load()?.get(&metadata_key).cloned();
+    let fully_cached = stored_metadata
+        .as_ref()
+        .map(|m| m.layers.iter().all(|digest| Path::new(&blob_store_path(digest)).exists()))
+        .unwrap_or(false);
+
+    // Ensure layers + config are in the content store, pulling whatever is missing.
+    let (download_wall_clock, download_summed, image_config, image_metadata) = if fully_cached {
+        println!("All layers for {} cached, skipping registry", image_name);
+        let image_metadata = stored_metadata.unwrap();
+        let image_config = read_cached_image_config(&image_metadata.config_digest)?;
+        (Duration::ZERO, Duration::ZERO, image_config, image_metadata)
+    } else {
+        pull_image(image, tag, platform_override, download_concurrency, registry)?
+    };
 
-    Ok((root_fs_path, download_duration, unpack_duration))
+    match rootfs_mode {
+        RootFsMode::Overlay => {
+            let merged_dir = assemble_root_fs_with_overlay(&image_metadata, &root_fs_path)?;
+            Ok((
+                merged_dir,
+                download_wall_clock,
+                download_summed,
+                Duration::ZERO,
+                image_config,
+            ))
+        }
+        RootFsMode::Copy => {
+            if Path::new(&root_fs_path).exists() {
+                return Ok((
+                    root_fs_path,
+                    download_wall_clock,
+                    download_summed,
+                    Duration::ZERO,
+                    image_config,
+                ));
+            }
+            let unpack_duration = assemble_root_fs_from_cache(&image_metadata, &root_fs_path)?;
+            Ok((
+                root_fs_path,
+                download_wall_clock,
+                download_summed,
+                unpack_duration,
+                image_config,
+            ))
+        }
+    }
 }
 
-// Starts the container using Linux Namespaces and pivot_root with mounts
+// Starts the container using Linux Namespaces and pivot_root with mounts, execing the entrypoint
+// + cmd the image actually asks for (with its env, workdir and user) instead of a fixed script.
 fn start_container(
     root_fs: &str,
     mounts: Option<Vec<Mount>>,
+    image_config: &ImageConfig,
+    ready_write: Option<i32>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use nix::unistd::Uid;
-
     // Unshare process namespaces for isolation
     unshare(
         CloneFlags::CLONE_NEWNS
@@ -332,26 +1391,48 @@ fn start_container(
     match fork()? {
         ForkResult::Parent { child } => {
             println!("Container started with PID: {}", child);
+            // Tell whoever is supervising us (e.g. `run_service`) that the workload has actually
+            // launched, so they can move on instead of blocking until it exits.
+            if let Some(fd) = ready_write {
+                let _ = write(fd, &[1u8]);
+                let _ = close(fd);
+            }
             waitpid(child, None)?; // Wait for the child process
         }
         ForkResult::Child => {
-            // Drop privileges (optional but recommended)
+            // The workload doesn't need the readiness pipe; avoid leaking it across the exec.
+            if let Some(fd) = ready_write {
+                let _ = close(fd);
+            }
+            // Drop privileges to the image's configured user (default "nobody" if unset).
+            let (uid, gid) = resolve_user(image_config);
             if Uid::effective() == Uid::root() {
-                nix::unistd::setgid(nix::unistd::Gid::from_raw(65534))?; // nobody
-                nix::unistd::setuid(nix::unistd::Uid::from_raw(65534))?;
+                nix::unistd::setgid(gid)?;
+                nix::unistd::setuid(uid)?;
+            }
+
+            // chdir into the image's configured working directory, if any.
+            if let Some(working_dir) = &image_config.working_dir {
+                if !working_dir.is_empty() {
+                    chdir(Path::new(working_dir))?;
+                }
             }
 
-            // Execute the container's entrypoint command
-            let cmd = CString::new("/bin/sh").unwrap();
-            let args = [
-                CString::new("sh").unwrap(), // argv[0], the program name
-                CString::new("-c").unwrap(),
-                CString::new(
-                    "ip link set lo up && echo Hello from container! && sleep 10",
-                )
-                .unwrap(),
-            ];
-            execvp(&cmd, &args)?;
+            // Execute the image's real entrypoint (entrypoint + cmd concatenated per OCI rules).
+            let argv = resolve_argv(image_config);
+            let program = CString::new(argv[0].as_str())?;
+            let args: Vec<CString> = argv
+                .iter()
+                .map(|arg| CString::new(arg.as_str()))
+                .collect::<Result<_, _>>()?;
+            let env: Vec<CString> = image_config
+                .env
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .map(|entry| CString::new(entry.as_str()))
+                .collect::<Result<_, _>>()?;
+            execvpe(&program, &args, &env)?;
         }
     }
 